@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::fail::HResult;
+
+/// Implemented by widgets whose refresh splits into a pure, blocking "go do
+/// the IO" half -- safe to run off the input thread -- and a cheap "apply
+/// the result" half that runs back on the widget once the job completes.
+pub trait AsyncRefresh {
+    type Update: Send + 'static;
+
+    fn refresh_job(&self) -> Box<dyn FnOnce() -> HResult<Self::Update> + Send>;
+    fn apply_refresh(&mut self, update: Self::Update) -> HResult<()>;
+}
+
+struct Done<K, U> {
+    key: K,
+    update: HResult<U>
+}
+
+/// Coordinates background refresh jobs for a `TabView`. At most one job per
+/// key is ever in flight; a refresh request for a key that's already
+/// reloading is coalesced into the job already running instead of starting
+/// a second one.
+///
+/// `K` identifies *a tab*, not a tab-view slot -- callers should use a
+/// token that stays with a tab across closes/moves, since a plain vec index
+/// gets reused by whatever tab ends up at that position next.
+pub struct RefreshQueue<K, U> {
+    runtime: Runtime,
+    tx: UnboundedSender<Done<K, U>>,
+    rx: UnboundedReceiver<Done<K, U>>,
+    pending: HashSet<K>
+}
+
+impl<K: Eq + Hash + Copy + Send + 'static, U: Send + 'static> RefreshQueue<K, U> {
+    pub fn new() -> HResult<RefreshQueue<K, U>> {
+        // Jobs only ever run via `spawn_blocking`, which uses its own
+        // blocking thread pool regardless of runtime flavor -- there are no
+        // worker threads or a reactor to configure here.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .thread_name("hunter-refresh")
+            .build()?;
+        let (tx, rx) = unbounded_channel();
+
+        Ok(RefreshQueue {
+            runtime,
+            tx,
+            rx,
+            pending: HashSet::new()
+        })
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.runtime.handle().clone()
+    }
+
+    pub fn is_loading(&self, key: K) -> bool {
+        self.pending.contains(&key)
+    }
+
+    /// Enqueue `job` as a background reload for `key`, unless one is
+    /// already in flight for it.
+    pub fn enqueue<F>(&mut self, key: K, job: F)
+        where F: FnOnce() -> HResult<U> + Send + 'static
+    {
+        if !self.pending.insert(key) {
+            return;
+        }
+
+        let tx = self.tx.clone();
+        self.runtime.spawn_blocking(move || {
+            let update = job();
+            tx.send(Done { key, update }).ok();
+        });
+    }
+
+    /// Drain finished jobs, handing back each key with its result so the
+    /// caller can apply it to the right widget and clear its spinner. A key
+    /// whose tab has since been closed is the caller's to discard.
+    pub fn poll(&mut self) -> Vec<(K, HResult<U>)> {
+        let mut done = vec![];
+
+        while let Ok(finished) = self.rx.try_recv() {
+            self.pending.remove(&finished.key);
+            done.push((finished.key, finished.update));
+        }
+
+        done
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use crate::fail::HResult;
+
+/// Directory hunter keeps its config/session files under. Respects
+/// `$XDG_CONFIG_HOME` and falls back to `$HOME/.config`, like the rest of
+/// the XDG-aware ecosystem.
+pub fn config_dir() -> HResult<PathBuf> {
+    let xdg_config_home = std::env::var("XDG_CONFIG_HOME").ok().filter(|v| !v.is_empty());
+
+    let mut path = match xdg_config_home {
+        Some(xdg) => PathBuf::from(xdg),
+        None => {
+            let mut home = PathBuf::from(std::env::var("HOME")?);
+            home.push(".config");
+            home
+        }
+    };
+
+    path.push("hunter");
+    Ok(path)
+}
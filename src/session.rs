@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fail::HResult;
+
+/// On-disk representation of an open `TabView`: the directory each tab was
+/// in, and which one was active. Restored on startup, written out on exit.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Session {
+    pub tabs: Vec<PathBuf>,
+    pub active: usize
+}
+
+impl Session {
+    pub fn from_tabs(tabs: Vec<PathBuf>, active: usize) -> Session {
+        Session { tabs, active }
+    }
+
+    pub fn save(&self) -> HResult<()> {
+        let serialized = toml::to_string_pretty(self)?;
+        let path = Session::file()?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, serialized)?;
+        Ok(())
+    }
+
+    /// Load the saved session, falling back to an empty one (which callers
+    /// turn into a single default tab) on a missing, corrupt, or otherwise
+    /// unreadable session file.
+    pub fn load() -> Session {
+        Session::try_load().unwrap_or_default()
+    }
+
+    fn try_load() -> HResult<Session> {
+        let path = Session::file()?;
+        let content = fs::read_to_string(path)?;
+        let session = toml::from_str(&content)?;
+        Ok(session)
+    }
+
+    fn file() -> HResult<PathBuf> {
+        let mut path = crate::paths::config_dir()?;
+        path.push("session.toml");
+        Ok(path)
+    }
+}
@@ -1,7 +1,12 @@
+use std::collections::HashMap;
+
 use termion::event::Key;
 
 use crate::widget::{Widget, WidgetCore};
 use crate::fail::{HResult, ErrorLog};
+use crate::watch::{TabWatcher, Watchable};
+use crate::session::Session;
+use crate::async_refresh::{AsyncRefresh, RefreshQueue};
 
 pub trait Tabbable {
     fn new_tab(&mut self) -> HResult<()>;
@@ -10,6 +15,9 @@ pub trait Tabbable {
     fn on_next_tab(&mut self) -> HResult<()> {
         Ok(())
     }
+    fn goto_tab(&mut self, index: usize) -> HResult<()>;
+    fn move_tab(&mut self, from: usize, to: usize) -> HResult<()>;
+    fn active_index(&self) -> usize;
     fn get_tab_names(&self) -> Vec<Option<String>>;
     fn active_tab(&self) -> &dyn Widget;
     fn active_tab_mut(&mut self) -> &mut dyn Widget;
@@ -19,39 +27,230 @@ pub trait Tabbable {
             Key::Ctrl('t') => self.new_tab(),
             Key::Ctrl('w') => self.close_tab(),
             Key::Char('\t') => self.next_tab(),
+            Key::Alt(c) if c.is_ascii_digit() => {
+                let index = c.to_digit(10).unwrap() as usize;
+                self.goto_tab(index)
+            },
+            Key::Alt('<') => {
+                let from = self.active_index();
+                self.move_tab(from, from.saturating_sub(1))
+            },
+            Key::Alt('>') => {
+                let from = self.active_index();
+                self.move_tab(from, from + 1)
+            },
             _ => self.on_key_sub(key)
         }
     }
 }
 
 
-#[derive(PartialEq)]
-pub struct TabView<T> where T: Widget, TabView<T>: Tabbable {
+pub struct TabView<T> where T: Widget + AsyncRefresh, TabView<T>: Tabbable {
     pub widgets: Vec<T>,
     pub active: usize,
-    core: WidgetCore
+    core: WidgetCore,
+    watcher: Option<TabWatcher>,
+    dirty: Vec<bool>,
+    // Bumped every time a tab is marked dirty, so a refresh that was already
+    // in flight when a newer change landed can tell its result is stale.
+    dirty_version: Vec<u64>,
+    loading: Vec<bool>,
+    // Identifies a tab across closes/moves -- unlike a vec index, a token is
+    // never reused, so a job's result can't land on the wrong tab.
+    tokens: Vec<u64>,
+    next_token: u64,
+    refresh_queue: Option<RefreshQueue<u64, T::Update>>,
+    inflight_version: HashMap<u64, u64>,
+    redraw_pending: bool
+}
+
+impl<T> PartialEq for TabView<T> where T: Widget + AsyncRefresh + PartialEq, TabView<T>: Tabbable {
+    fn eq(&self, other: &Self) -> bool {
+        self.widgets == other.widgets && self.active == other.active
+    }
 }
 
-impl<T> TabView<T> where T: Widget, TabView<T>: Tabbable {
+impl<T> TabView<T> where T: Widget + Watchable + AsyncRefresh, TabView<T>: Tabbable {
     pub fn new(core: &WidgetCore) -> TabView<T> {
         TabView {
             widgets: vec![],
             active: 0,
-            core: core.clone()
+            core: core.clone(),
+            watcher: TabWatcher::new().ok(),
+            dirty: vec![],
+            dirty_version: vec![],
+            loading: vec![],
+            tokens: vec![],
+            next_token: 0,
+            refresh_queue: RefreshQueue::new().ok(),
+            inflight_version: HashMap::new(),
+            redraw_pending: false
         }
     }
 
     pub fn push_widget(&mut self, widget: T) -> HResult<()> {
         self.widgets.push(widget);
+        self.dirty.push(false);
+        self.dirty_version.push(0);
+        self.loading.push(false);
+        self.tokens.push(self.next_token);
+        self.next_token += 1;
+        self.register_watch(self.widgets.len() - 1);
         self.refresh()
     }
 
     pub fn pop_widget(&mut self) -> HResult<T> {
         let widget = self.widgets.pop()?;
+        self.dirty.pop();
+        self.dirty_version.pop();
+        self.loading.pop();
+        self.tokens.pop();
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.unregister(self.widgets.len());
+        }
         self.refresh()?;
         Ok(widget)
     }
 
+    fn register_watch(&mut self, tab: usize) {
+        if let (Some(watcher), Some(widget)) = (self.watcher.as_mut(), self.widgets.get(tab)) {
+            if let Some(path) = widget.watched_path() {
+                watcher.register(tab, &path);
+            }
+        }
+    }
+
+    /// Re-register the watch for `tab` after it navigated to a new
+    /// directory (the old registration is replaced, not stacked).
+    pub fn rewatch_tab(&mut self, tab: usize) {
+        let path = self.widgets.get(tab).and_then(|w| w.watched_path());
+
+        if let (Some(watcher), Some(path)) = (self.watcher.as_mut(), path) {
+            watcher.rewatch(tab, &path);
+        }
+    }
+
+    /// Drain pending watcher events, flag the affected tabs dirty, and
+    /// enqueue a background refresh for whichever of them is currently
+    /// active. Tabs that aren't active just stay dirty until they're
+    /// activated, via `on_next_tab`/`goto_tab_`.
+    pub fn process_watch_events(&mut self) -> HResult<()> {
+        let dirty_tabs = match self.watcher.as_mut() {
+            Some(watcher) => watcher.dirty_tabs(),
+            None => return Ok(())
+        };
+
+        for tab in dirty_tabs {
+            self.mark_dirty(tab);
+        }
+
+        self.refresh_if_dirty(self.active);
+
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, tab: usize) {
+        if let Some(dirty) = self.dirty.get_mut(tab) {
+            *dirty = true;
+        }
+        if let Some(version) = self.dirty_version.get_mut(tab) {
+            *version += 1;
+        }
+    }
+
+    /// Enqueue a background reload of `tab`, unless one's already in
+    /// flight for it -- multiple pending refreshes for the same tab
+    /// coalesce into whichever job is already running.
+    fn enqueue_refresh(&mut self, tab: usize) {
+        let token = match self.tokens.get(tab) {
+            Some(&token) => token,
+            None => return
+        };
+        let (queue, widget) = match (self.refresh_queue.as_mut(), self.widgets.get(tab)) {
+            (Some(queue), Some(widget)) => (queue, widget),
+            _ => return
+        };
+
+        if queue.is_loading(token) {
+            return;
+        }
+
+        queue.enqueue(token, widget.refresh_job());
+        self.inflight_version.insert(token, self.dirty_version.get(tab).copied().unwrap_or(0));
+
+        if let Some(loading) = self.loading.get_mut(tab) {
+            *loading = true;
+        }
+    }
+
+    /// Apply any background refreshes that finished since the last poll,
+    /// clearing their tab's dirty/loading state and flagging a redraw. A
+    /// finished job whose tab has since been closed is simply discarded;
+    /// one whose tab went dirty again while the job was in flight keeps its
+    /// dirty flag set, since the result it's about to apply predates that
+    /// change. A job that failed is only logged: its tab's loading state is
+    /// cleared, but it stays dirty and no redraw is flagged.
+    fn apply_finished_refreshes(&mut self) -> HResult<()> {
+        let done = match self.refresh_queue.as_mut() {
+            Some(queue) => queue.poll(),
+            None => return Ok(())
+        };
+
+        for (token, update) in done {
+            let enqueued_at = self.inflight_version.remove(&token);
+            let tab = match self.tokens.iter().position(|&t| t == token) {
+                Some(tab) => tab,
+                None => continue
+            };
+
+            if let Some(loading) = self.loading.get_mut(tab) {
+                *loading = false;
+            }
+
+            match update {
+                Ok(update) => {
+                    if let Some(widget) = self.widgets.get_mut(tab) {
+                        // A bad apply on one tab shouldn't strand the rest
+                        // of this batch -- log it and keep draining.
+                        if let Err(err) = widget.apply_refresh(update) {
+                            err.log();
+                        }
+                    }
+
+                    let current_version = self.dirty_version.get(tab).copied();
+                    if enqueued_at == current_version {
+                        if let Some(dirty) = self.dirty.get_mut(tab) {
+                            *dirty = false;
+                        }
+                    } else {
+                        // The tab changed again while this job was in
+                        // flight, so the result we just applied is already
+                        // stale -- kick off a fresh reload right away
+                        // instead of waiting for the next keypress/watch
+                        // event/activation.
+                        self.enqueue_refresh(tab);
+                    }
+
+                    self.redraw_pending = true;
+                },
+                Err(err) => err.log()
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a background refresh has completed since this was last
+    /// checked; only this call clears it. `refresh()` (driven by every
+    /// keypress via `Widget::on_key`) already applies finished jobs, but a result can land
+    /// while the input thread is blocked waiting on the next key -- callers
+    /// that want the screen to update the instant that happens, rather than
+    /// on the next keypress, need to poll this directly on a timeout/select
+    /// alongside the input read and redraw when it's true.
+    pub fn needs_redraw(&mut self) -> bool {
+        std::mem::replace(&mut self.redraw_pending, false)
+    }
+
     pub fn active_tab_(&self) -> &T {
         &self.widgets[self.active]
     }
@@ -62,7 +261,7 @@ impl<T> TabView<T> where T: Widget, TabView<T>: Tabbable {
 
     pub fn close_tab_(&mut self) -> HResult<()> {
         self.pop_widget()?;
-        self.active -= 1;
+        self.active = self.clamp_active(self.active.saturating_sub(1));
         Ok(())
     }
 
@@ -73,10 +272,142 @@ impl<T> TabView<T> where T: Widget, TabView<T>: Tabbable {
             self.active += 1
         }
         self.on_next_tab().log();
+        self.refresh_if_dirty(self.active);
+    }
+
+    /// Jump directly to `index`, e.g. via `Alt-<n>`. Out-of-range indices
+    /// are ignored rather than clamped, so a stray keypress can't jump you
+    /// to the wrong tab.
+    pub fn goto_tab_(&mut self, index: usize) -> HResult<()> {
+        if index >= self.widgets.len() || index == self.active {
+            return Ok(());
+        }
+
+        self.active = index;
+        self.on_next_tab().log();
+        self.refresh_if_dirty(self.active);
+        Ok(())
+    }
+
+    /// Move the tab at `from` to `to`, shifting the tabs in between and
+    /// keeping `active` pointed at the same tab it was before the move.
+    pub fn move_tab_(&mut self, from: usize, to: usize) -> HResult<()> {
+        if from >= self.widgets.len() || from == to {
+            return Ok(());
+        }
+        let to = self.clamp_active(to);
+
+        let widget = self.widgets.remove(from);
+        self.widgets.insert(to, widget);
+        let dirty = self.dirty.remove(from);
+        self.dirty.insert(to, dirty);
+        let dirty_version = self.dirty_version.remove(from);
+        self.dirty_version.insert(to, dirty_version);
+        let loading = self.loading.remove(from);
+        self.loading.insert(to, loading);
+        let token = self.tokens.remove(from);
+        self.tokens.insert(to, token);
+
+        if self.active == from {
+            self.active = to;
+        } else if from < self.active && self.active <= to {
+            self.active -= 1;
+        } else if to <= self.active && self.active < from {
+            self.active += 1;
+        }
+
+        self.resync_watches();
+        Ok(())
+    }
+
+    fn refresh_if_dirty(&mut self, tab: usize) {
+        if self.dirty.get(tab) == Some(&true) {
+            self.enqueue_refresh(tab);
+        }
+    }
+
+    fn clamp_active(&self, active: usize) -> usize {
+        active.min(self.widgets.len().saturating_sub(1))
+    }
+
+    fn resync_watches(&mut self) {
+        for tab in 0..self.widgets.len() {
+            self.register_watch(tab);
+        }
+    }
+
+    /// Serialize the current tabs (working directory of each, plus which
+    /// one is active) to the session file, so they can be restored on the
+    /// next start. Neither this nor `restore_session` is called from
+    /// anywhere on its own -- the top-level startup/shutdown path is
+    /// expected to call `restore_session` once in place of the initial
+    /// `new_tab` and `save_session` on exit.
+    pub fn save_session(&self) -> HResult<()> {
+        let mut tabs = vec![];
+        let mut active = 0;
+
+        // Tabs with nothing to watch aren't saved, so the active index is
+        // counted over the saved tabs only.
+        for (index, widget) in self.widgets.iter().enumerate() {
+            if let Some(path) = widget.watched_path() {
+                if index < self.active {
+                    active += 1;
+                }
+                tabs.push(path);
+            }
+        }
+
+        Session::from_tabs(tabs, active).save()
+    }
+
+    /// Rebuild the tab set from the saved session. A saved path that no
+    /// longer exists (or isn't accessible) is skipped rather than aborting
+    /// the whole restore; an empty or corrupt session just falls back to
+    /// whatever `new_tab` opens by default. Each restored tab is marked
+    /// dirty so it actually loads the saved directory instead of keeping
+    /// whatever `new_tab` opened it on.
+    ///
+    /// Skipped tabs shift everything after them down, so `session.active`
+    /// (an index into the *saved* list) is remapped to wherever that same
+    /// tab landed in the restored one -- or, if it was itself skipped, the
+    /// first surviving tab saved after it (the last surviving tab if none
+    /// follows).
+    pub fn restore_session(&mut self) -> HResult<()> where Self: Tabbable {
+        let session = Session::load();
+        let mut kept = vec![];
+
+        for (saved_index, path) in session.tabs.iter().enumerate().filter(|(_, path)| path.is_dir()) {
+            self.new_tab()?;
+
+            let tab = self.widgets.len() - 1;
+            match self.widgets[tab].set_watched_path(path) {
+                Ok(()) => {
+                    self.register_watch(tab);
+                    self.mark_dirty(tab);
+                },
+                Err(err) => err.log()
+            }
+
+            kept.push(saved_index);
+        }
+
+        if self.widgets.is_empty() {
+            self.new_tab()?;
+        } else {
+            self.active = kept.iter()
+                .position(|&saved_index| saved_index >= session.active)
+                .unwrap_or(kept.len() - 1);
+        }
+
+        self.active = self.clamp_active(self.active);
+        self.on_next_tab().log();
+        self.refresh_if_dirty(self.active);
+
+        Ok(())
     }
 }
 
-impl<T> Widget for TabView<T> where T: Widget, TabView<T>: Tabbable {
+impl<T> Widget for TabView<T> where T: Widget + Watchable + AsyncRefresh, TabView<T>: Tabbable {
     fn get_core(&self) -> HResult<&WidgetCore> {
         Ok(&self.core)
     }
@@ -89,18 +420,23 @@ impl<T> Widget for TabView<T> where T: Widget, TabView<T>: Tabbable {
         let tab_names = self.get_tab_names();
         let mut nums_length = 0;
         let tabnums = (0..self.widgets.len()).map(|num| {
-            nums_length += format!("{}:{} ",
+            let spinner = if self.loading.get(num) == Some(&true) { " *" } else { "" };
+
+            nums_length += format!("{}:{}{} ",
                                    num,
-                                   tab_names[num].as_ref().unwrap()).len();
+                                   tab_names[num].as_ref().unwrap(),
+                                   spinner).len();
+
             if num == self.active {
-                format!(" {}{}:{}{}{}",
+                format!(" {}{}:{}{}{}{}",
                         crate::term::invert(),
                         num,
                         tab_names[num].as_ref().unwrap(),
+                        spinner,
                         crate::term::reset(),
                         crate::term::header_color())
             } else {
-                format!(" {}:{}", num, tab_names[num].as_ref().unwrap())
+                format!(" {}:{}{}", num, tab_names[num].as_ref().unwrap(), spinner)
             }
         }).collect::<String>();
 
@@ -118,8 +454,16 @@ impl<T> Widget for TabView<T> where T: Widget, TabView<T>: Tabbable {
         self.active_tab_().render_footer()
     }
 
+    /// Enqueues a background reload of the active tab and returns
+    /// immediately -- the actual directory-listing/stat work happens off
+    /// the input thread, so a slow or networked directory never blocks the
+    /// UI. Results that finished since the last call are applied here too,
+    /// before the new refresh is kicked off.
     fn refresh(&mut self) -> HResult<()> {
-        self.active_tab_mut().refresh()
+        self.process_watch_events().log();
+        self.apply_finished_refreshes()?;
+        self.enqueue_refresh(self.active);
+        Ok(())
     }
 
     fn get_drawlist(&self) -> HResult<String> {
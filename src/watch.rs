@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::fail::{ErrorLog, HResult};
+
+// Coalesce bursts (e.g. a large extraction) into a single refresh per tab.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Implemented by widgets that can be watched for filesystem changes while
+/// sitting in a background tab. Returning `None` means "nothing to watch"
+/// (e.g. a widget that isn't backed by a directory).
+pub trait Watchable {
+    fn watched_path(&self) -> Option<PathBuf>;
+
+    /// Point the widget at a different directory, e.g. when restoring a
+    /// saved session.
+    fn set_watched_path(&mut self, path: &Path) -> HResult<()>;
+}
+
+/// Watches the working directory of every tab in a `TabView` and reports
+/// which tabs went dirty, so they can be refreshed without the user having
+/// to cycle back to them first.
+pub struct TabWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    // The path each tab is currently watching.
+    tabs: HashMap<usize, PathBuf>,
+    // How many tabs are watching a given path, so two tabs open on the same
+    // directory don't unwatch it out from under each other.
+    refcounts: HashMap<PathBuf, usize>,
+}
+
+impl TabWatcher {
+    pub fn new() -> HResult<TabWatcher> {
+        let (tx, rx) = channel();
+        let watcher = Watcher::new(tx, DEBOUNCE)?;
+        Ok(TabWatcher {
+            watcher,
+            events: rx,
+            tabs: HashMap::new(),
+            refcounts: HashMap::new(),
+        })
+    }
+
+    /// Start watching `path` on behalf of `tab`, replacing whatever that tab
+    /// was previously watching. A path that can't be watched (already gone,
+    /// permission denied, etc.) just means this tab won't get live updates.
+    pub fn register(&mut self, tab: usize, path: &Path) {
+        self.unregister(tab);
+
+        let path = path.to_path_buf();
+        let already_watched = self.refcounts.contains_key(&path);
+
+        if !already_watched {
+            if let Err(err) = self.watcher.watch(&path, RecursiveMode::NonRecursive) {
+                err.log();
+                return;
+            }
+        }
+
+        *self.refcounts.entry(path.clone()).or_insert(0) += 1;
+        self.tabs.insert(tab, path);
+    }
+
+    /// Stop watching whatever `tab` was registered for, if anything. The
+    /// underlying path stays watched as long as another tab still needs it.
+    pub fn unregister(&mut self, tab: usize) {
+        let path = match self.tabs.remove(&tab) {
+            Some(path) => path,
+            None => return,
+        };
+
+        if let Some(refs) = self.refcounts.get_mut(&path) {
+            *refs -= 1;
+            if *refs == 0 {
+                self.refcounts.remove(&path);
+                self.watcher.unwatch(&path).log();
+            }
+        }
+    }
+
+    /// Re-point the watch for `tab` at `to`, e.g. after the tab navigated to
+    /// a new directory.
+    pub fn rewatch(&mut self, tab: usize, to: &Path) {
+        self.register(tab, to);
+    }
+
+    /// Drain pending filesystem events and return the (deduplicated) tabs
+    /// that were touched. A path whose watch has gone stale (the directory
+    /// was removed or unmounted) is dropped rather than kept around to error
+    /// on every poll.
+    pub fn dirty_tabs(&mut self) -> Vec<usize> {
+        let mut dirty = vec![];
+
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.handle_event(event, &mut dirty),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        dirty
+    }
+
+    fn handle_event(&mut self, event: DebouncedEvent, dirty: &mut Vec<usize>) {
+        match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Chmod(path) => {
+                self.mark_dirty(&path, dirty);
+            }
+            DebouncedEvent::Remove(path) => {
+                self.mark_dirty(&path, dirty);
+
+                if self.refcounts.contains_key(&path) {
+                    // The watched directory itself disappeared (unmount,
+                    // delete) -- drop every tab pointed at it instead of
+                    // leaving them to error on every poll.
+                    self.refcounts.remove(&path);
+                    self.tabs.retain(|_, watched| *watched != path);
+                }
+            }
+            DebouncedEvent::Rename(from, to) => {
+                if let Some(refs) = self.refcounts.remove(&from) {
+                    // `from` was itself a watched directory (not just a
+                    // child path renamed within one) -- move the actual OS
+                    // watch over to `to` so it keeps matching our bookkeeping.
+                    if let Err(err) = self.watcher.watch(&to, RecursiveMode::NonRecursive) {
+                        err.log();
+                    }
+                    self.watcher.unwatch(&from).log();
+
+                    self.refcounts.insert(to.clone(), refs);
+                    for watched in self.tabs.values_mut() {
+                        if *watched == from {
+                            *watched = to.clone();
+                        }
+                    }
+                }
+                self.mark_dirty(&to, dirty);
+            }
+            _ => {}
+        }
+    }
+
+    /// Flag every tab watching `path` (or an ancestor of it) as dirty.
+    fn mark_dirty(&self, path: &Path, dirty: &mut Vec<usize>) {
+        for (&tab, watched) in self.tabs.iter() {
+            if path.starts_with(watched.as_path()) && !dirty.contains(&tab) {
+                dirty.push(tab);
+            }
+        }
+    }
+}